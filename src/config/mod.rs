@@ -0,0 +1,3 @@
+//! Configuration types for kakehashi.
+
+pub mod settings;
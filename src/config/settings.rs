@@ -0,0 +1,45 @@
+//! Configuration for downstream bridge servers.
+
+use std::path::PathBuf;
+
+/// Configuration for a downstream (bridge) language server kakehashi
+/// forwards requests to for injected regions (e.g. a Lua language server for
+/// Lua code blocks embedded in Markdown).
+#[derive(Debug, Clone)]
+pub struct BridgeServerConfig {
+    /// Name used to look up and dedupe connections/installs for this server
+    /// (e.g. `"lua-language-server"`).
+    pub name: String,
+    /// Program to spawn.
+    pub command: String,
+    /// Arguments passed to `command`.
+    pub args: Vec<String>,
+    /// How to obtain `command` on demand if it isn't already installed.
+    /// `None` means the binary is assumed to already be on `PATH`.
+    pub download: Option<BridgeServerDownload>,
+}
+
+/// Describes how to acquire a bridge server's binary: where to download it
+/// from, how it's packaged, and which version is expected.
+#[derive(Debug, Clone)]
+pub struct BridgeServerDownload {
+    /// Version string recorded alongside the cached binary so a version
+    /// bump triggers a fresh acquisition.
+    pub version: String,
+    /// URL to download the archive (or raw binary) from.
+    pub url: String,
+    /// How the downloaded artifact is packaged.
+    pub archive: ArchiveKind,
+    /// Path of the executable inside the extracted archive; for
+    /// `ArchiveKind::Raw`, the name the downloaded file is cached under.
+    pub binary_path_in_archive: PathBuf,
+}
+
+/// Packaging format of a downloaded bridge server artifact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    TarGz,
+    Zip,
+    /// The downloaded file is the executable itself, no extraction needed.
+    Raw,
+}
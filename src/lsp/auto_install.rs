@@ -8,13 +8,38 @@
 //! - `InstallingLanguages`: Type alias for `InProgressSet<String>` tracking concurrent installs
 //! - `InstallingLanguagesExt`: Extension trait providing domain-specific method names
 //! - `AutoInstallManager`: Isolated coordinator for installation
+//!
+//! # Retry on a broken install
+//!
+//! A freshly installed parser/query can still be unusable (partial download,
+//! ABI-incompatible grammar, malformed query file). `AutoInstallManager`
+//! validates the install before releasing it with `finish_install` and, on failure, schedules a
+//! bounded re-install using [`install_retry_backoff`] before giving up and
+//! emitting a terminal `InstallEvent::GaveUp`. The retry reuses the same
+//! `InstallingLanguages` entry so concurrent opens of the same language don't
+//! each trigger their own re-install.
 
 mod manager;
 
-pub(crate) use manager::{AutoInstallManager, InstallEvent};
+pub(crate) use manager::{AutoInstallManager, InstallError, InstallEvent, InstallOps};
 
 use crate::lsp::in_progress_set::InProgressSet;
 
+/// Maximum number of automatic re-install attempts after an installed
+/// parser/query fails post-install validation, not counting the initial
+/// install itself.
+pub(crate) const MAX_INSTALL_RETRY_ATTEMPTS: u32 = 3;
+
+/// Backoff delay before the `attempt`-th re-install (1-indexed).
+///
+/// Attempt 1 waits 1s, attempt 2 waits 4s, attempt 3 and beyond wait the
+/// capped 16s. Used by `AutoInstallManager` to space out retries after a
+/// validation failure instead of hammering the source immediately.
+pub(crate) fn install_retry_backoff(attempt: u32) -> std::time::Duration {
+    let secs = 4u64.saturating_pow(attempt.saturating_sub(1).min(2));
+    std::time::Duration::from_secs(secs)
+}
+
 /// Tracks languages currently being installed to prevent duplicate installs.
 ///
 /// This is a type alias for `InProgressSet<String>`, providing domain-specific
@@ -40,3 +65,23 @@ impl InstallingLanguagesExt for InstallingLanguages {
         self.finish(&language.to_string());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn install_retry_backoff_follows_1s_4s_16s_schedule() {
+        assert_eq!(install_retry_backoff(1), std::time::Duration::from_secs(1));
+        assert_eq!(install_retry_backoff(2), std::time::Duration::from_secs(4));
+        assert_eq!(install_retry_backoff(3), std::time::Duration::from_secs(16));
+    }
+
+    #[test]
+    fn install_retry_backoff_caps_beyond_max_attempts() {
+        assert_eq!(
+            install_retry_backoff(MAX_INSTALL_RETRY_ATTEMPTS + 5),
+            std::time::Duration::from_secs(16)
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Coordinates installing a language's parser/queries, independent of how
+//! the actual download/load/compile work is performed.
+//!
+//! `AutoInstallManager` is deliberately decoupled from the mechanics of
+//! fetching a grammar or compiling a query file: callers supply an
+//! `InstallOps` implementation, and the manager only owns the policy around
+//! it — dedupe concurrent installs, validate the result, retry with backoff
+//! on a broken install, and give up after too many failures.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use super::{install_retry_backoff, InstallingLanguages, InstallingLanguagesExt, MAX_INSTALL_RETRY_ATTEMPTS};
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Error returned by an `InstallOps` step. Carries a human-readable reason
+/// that is forwarded verbatim into `InstallEvent::Failed`.
+#[derive(Debug, Clone)]
+pub(crate) struct InstallError(pub(crate) String);
+
+impl std::fmt::Display for InstallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InstallError {}
+
+/// The mechanics `AutoInstallManager` drives: how to install, validate, and
+/// purge a language's parser/queries. Implemented by the grammar-loading
+/// layer; kept behind a trait so the retry/backoff policy here can be tested
+/// without a real download.
+pub(crate) trait InstallOps: Send + Sync {
+    /// Download/build and place the parser + queries for `language`.
+    fn install<'a>(&'a self, language: &'a str) -> BoxFuture<'a, Result<(), InstallError>>;
+
+    /// Attempt to actually load the installed parser and compile its
+    /// queries, to catch a partial download or an ABI-incompatible build.
+    fn validate<'a>(&'a self, language: &'a str) -> BoxFuture<'a, Result<(), InstallError>>;
+
+    /// Remove whatever `install` placed for `language`, so a retry starts
+    /// from a clean slate.
+    fn purge<'a>(&'a self, language: &'a str) -> BoxFuture<'a, Result<(), InstallError>>;
+}
+
+/// Progress/outcome events emitted while a language is (re-)installed.
+///
+/// The LSP layer forwards these as `$/progress` notifications, and turns a
+/// terminal `GaveUp` into a diagnostic so the user isn't left silently
+/// served a broken language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum InstallEvent {
+    /// `language`'s install started.
+    Started(String),
+    /// `language` installed and passed post-install validation.
+    Installed(String),
+    /// An install or validation attempt for `language` failed; a retry may
+    /// still follow (see `GaveUp` for the terminal case).
+    Failed { language: String, reason: String },
+    /// `language` exhausted its retry budget; installation has been
+    /// abandoned.
+    GaveUp { language: String, attempts: u32 },
+}
+
+/// Isolated coordinator for installation: owns dedupe + retry/backoff policy
+/// and delegates the actual work to an injected `InstallOps`.
+pub(crate) struct AutoInstallManager {
+    installing: InstallingLanguages,
+    ops: Arc<dyn InstallOps>,
+    events: UnboundedSender<InstallEvent>,
+}
+
+impl AutoInstallManager {
+    /// Create a manager around `ops`, returning it alongside the receiving
+    /// end of its `InstallEvent` stream.
+    pub(crate) fn new(ops: Arc<dyn InstallOps>) -> (Self, UnboundedReceiver<InstallEvent>) {
+        let (events, receiver) = mpsc::unbounded_channel();
+        (
+            Self {
+                installing: InstallingLanguages::new(),
+                ops,
+                events,
+            },
+            receiver,
+        )
+    }
+
+    /// Install `language` if no install for it is already in progress,
+    /// validating the result and retrying with backoff
+    /// (see `install_retry_backoff`) if validation fails, up to
+    /// `MAX_INSTALL_RETRY_ATTEMPTS` times before emitting `InstallEvent::GaveUp`.
+    ///
+    /// Returns immediately (without emitting anything) if another caller is
+    /// already installing this language — callers that need to know the
+    /// outcome should watch the `InstallEvent` stream rather than depend on
+    /// every call path here. This is what lets a user who opened a file
+    /// during a flaky download end up with a working parser without having
+    /// to restart the editor: whichever call started the install also
+    /// carries it through revalidation and retry.
+    pub(crate) async fn install_language(&self, language: &str) {
+        if !self.installing.try_start_install(language) {
+            return;
+        }
+        self.emit(InstallEvent::Started(language.to_string()));
+
+        let mut attempt = 0u32;
+        loop {
+            let outcome = match self.ops.install(language).await {
+                Ok(()) => self.ops.validate(language).await,
+                Err(e) => Err(e),
+            };
+
+            match outcome {
+                Ok(()) => {
+                    self.installing.finish_install(language);
+                    self.emit(InstallEvent::Installed(language.to_string()));
+                    return;
+                }
+                Err(reason) => {
+                    self.emit(InstallEvent::Failed {
+                        language: language.to_string(),
+                        reason: reason.to_string(),
+                    });
+                    let _ = self.ops.purge(language).await;
+                }
+            }
+
+            attempt += 1;
+            // `attempt` counts the initial install's failure too, so giving
+            // up once it reaches `MAX_INSTALL_RETRY_ATTEMPTS` would only ever
+            // perform `MAX_INSTALL_RETRY_ATTEMPTS - 1` re-installs. Give up
+            // once it *exceeds* the budget instead, so exactly
+            // `MAX_INSTALL_RETRY_ATTEMPTS` re-installs happen after the
+            // initial one, matching its doc comment and actually reaching
+            // `install_retry_backoff`'s capped 16s tier.
+            if attempt > MAX_INSTALL_RETRY_ATTEMPTS {
+                self.installing.finish_install(language);
+                self.emit(InstallEvent::GaveUp {
+                    language: language.to_string(),
+                    attempts: attempt,
+                });
+                return;
+            }
+
+            tokio::time::sleep(install_retry_backoff(attempt)).await;
+        }
+    }
+
+    fn emit(&self, event: InstallEvent) {
+        // A dropped receiver just means nobody is watching progress right
+        // now; installation itself must not fail because of that.
+        let _ = self.events.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// `InstallOps` double that fails validation `fail_validations_remaining`
+    /// times before succeeding, so tests can exercise the retry path.
+    struct FlakyOps {
+        fail_validations_remaining: AtomicU32,
+        install_calls: AtomicU32,
+        purge_calls: AtomicU32,
+    }
+
+    impl InstallOps for FlakyOps {
+        fn install<'a>(&'a self, _language: &'a str) -> BoxFuture<'a, Result<(), InstallError>> {
+            self.install_calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok(()) })
+        }
+
+        fn validate<'a>(&'a self, _language: &'a str) -> BoxFuture<'a, Result<(), InstallError>> {
+            Box::pin(async {
+                if self
+                    .fail_validations_remaining
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                        if n == 0 {
+                            None
+                        } else {
+                            Some(n - 1)
+                        }
+                    })
+                    .is_ok()
+                {
+                    Err(InstallError("corrupt grammar".to_string()))
+                } else {
+                    Ok(())
+                }
+            })
+        }
+
+        fn purge<'a>(&'a self, _language: &'a str) -> BoxFuture<'a, Result<(), InstallError>> {
+            self.purge_calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_then_succeeds_after_validation_failures() {
+        let ops = Arc::new(FlakyOps {
+            fail_validations_remaining: AtomicU32::new(2),
+            install_calls: AtomicU32::new(0),
+            purge_calls: AtomicU32::new(0),
+        });
+        let (manager, mut events) = AutoInstallManager::new(ops.clone());
+
+        manager.install_language("lua").await;
+
+        assert_eq!(ops.install_calls.load(Ordering::SeqCst), 3);
+        assert_eq!(ops.purge_calls.load(Ordering::SeqCst), 2);
+
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+        assert_eq!(
+            received,
+            vec![
+                InstallEvent::Started("lua".to_string()),
+                InstallEvent::Failed {
+                    language: "lua".to_string(),
+                    reason: "corrupt grammar".to_string()
+                },
+                InstallEvent::Failed {
+                    language: "lua".to_string(),
+                    reason: "corrupt grammar".to_string()
+                },
+                InstallEvent::Installed("lua".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_max_attempts() {
+        let ops = Arc::new(FlakyOps {
+            fail_validations_remaining: AtomicU32::new(MAX_INSTALL_RETRY_ATTEMPTS + 10),
+            install_calls: AtomicU32::new(0),
+            purge_calls: AtomicU32::new(0),
+        });
+        let (manager, mut events) = AutoInstallManager::new(ops.clone());
+
+        manager.install_language("lua").await;
+
+        // The initial install plus `MAX_INSTALL_RETRY_ATTEMPTS` re-installs.
+        assert_eq!(
+            ops.install_calls.load(Ordering::SeqCst),
+            MAX_INSTALL_RETRY_ATTEMPTS + 1
+        );
+
+        let mut last = None;
+        while let Ok(event) = events.try_recv() {
+            last = Some(event);
+        }
+        assert_eq!(
+            last,
+            Some(InstallEvent::GaveUp {
+                language: "lua".to_string(),
+                attempts: MAX_INSTALL_RETRY_ATTEMPTS + 1
+            })
+        );
+    }
+
+    struct SlowOps {
+        install_calls: AtomicU32,
+    }
+
+    impl InstallOps for SlowOps {
+        fn install<'a>(&'a self, _language: &'a str) -> BoxFuture<'a, Result<(), InstallError>> {
+            self.install_calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                Ok(())
+            })
+        }
+
+        fn validate<'a>(&'a self, _language: &'a str) -> BoxFuture<'a, Result<(), InstallError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn purge<'a>(&'a self, _language: &'a str) -> BoxFuture<'a, Result<(), InstallError>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_install_of_same_language_only_runs_once() {
+        let ops = Arc::new(SlowOps {
+            install_calls: AtomicU32::new(0),
+        });
+        let manager = Arc::new(AutoInstallManager::new(ops.clone()).0);
+        let (m1, m2) = (manager.clone(), manager.clone());
+
+        tokio::join!(m1.install_language("lua"), m2.install_language("lua"));
+
+        assert_eq!(
+            ops.install_calls.load(Ordering::SeqCst),
+            1,
+            "second concurrent call should have been deduped by InstallingLanguages"
+        );
+    }
+}
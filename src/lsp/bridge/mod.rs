@@ -0,0 +1,6 @@
+//! Bridging requests for injected regions to downstream language servers.
+
+pub(crate) mod offset_encoding;
+pub(crate) mod pool;
+pub(crate) mod protocol;
+pub(crate) mod text_document;
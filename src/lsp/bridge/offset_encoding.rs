@@ -0,0 +1,168 @@
+//! Position-encoding negotiation and offset translation for virtual documents.
+//!
+//! Downstream servers negotiate an LSP `positionEncoding` (utf-8, utf-16, or
+//! utf-32) during their `initialize` handshake. Positions translated between
+//! a host document and a virtual document built by `VirtualDocumentUri::new`
+//! must account for that encoding, or diagnostics/completion edits come back
+//! shifted for any line containing non-ASCII content (emoji, CJK, ...).
+
+/// The position encoding a downstream connection negotiated during its
+/// `initialize` handshake.
+///
+/// Defaults to `Utf16`, the encoding the LSP spec falls back to when a
+/// server's `initialize` result omits `positionEncoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum OffsetEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl OffsetEncoding {
+    /// Parse the `positionEncoding` string returned in a downstream server's
+    /// `initialize` result, falling back to `Utf16` for anything absent or
+    /// unrecognized.
+    pub(crate) fn negotiate(position_encoding: Option<&str>) -> Self {
+        match position_encoding {
+            Some("utf-8") => Self::Utf8,
+            Some("utf-32") => Self::Utf32,
+            _ => Self::Utf16,
+        }
+    }
+
+    /// Code units a single `char` occupies under this encoding.
+    fn units(self, ch: char) -> u32 {
+        match self {
+            Self::Utf8 => ch.len_utf8() as u32,
+            Self::Utf16 => ch.len_utf16() as u32,
+            Self::Utf32 => 1,
+        }
+    }
+}
+
+/// Convert a `character` offset on `line` from `from`'s code units to `to`'s.
+///
+/// Walks `line`'s chars once, accumulating both encodings' unit counts in
+/// lockstep, and stops as soon as `from`'s count reaches `character`. Line
+/// numbers are encoding-independent, so only the `character` component of a
+/// `Position` needs this treatment.
+pub(crate) fn convert_character(
+    line: &str,
+    character: u32,
+    from: OffsetEncoding,
+    to: OffsetEncoding,
+) -> u32 {
+    if from == to {
+        return character;
+    }
+
+    let mut from_units = 0u32;
+    let mut to_units = 0u32;
+    for ch in line.chars() {
+        if from_units >= character {
+            break;
+        }
+        from_units += from.units(ch);
+        to_units += to.units(ch);
+    }
+    to_units
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMOJI_LINE: &str = "😀ab";
+    const CJK_LINE: &str = "中文ab";
+
+    #[test]
+    fn negotiate_defaults_to_utf16() {
+        assert_eq!(OffsetEncoding::negotiate(None), OffsetEncoding::Utf16);
+        assert_eq!(
+            OffsetEncoding::negotiate(Some("unknown")),
+            OffsetEncoding::Utf16
+        );
+    }
+
+    #[test]
+    fn negotiate_parses_known_encodings() {
+        assert_eq!(OffsetEncoding::negotiate(Some("utf-8")), OffsetEncoding::Utf8);
+        assert_eq!(
+            OffsetEncoding::negotiate(Some("utf-32")),
+            OffsetEncoding::Utf32
+        );
+    }
+
+    #[test]
+    fn same_encoding_is_identity() {
+        assert_eq!(
+            convert_character(EMOJI_LINE, 3, OffsetEncoding::Utf16, OffsetEncoding::Utf16),
+            3
+        );
+    }
+
+    #[test]
+    fn converts_past_emoji_between_all_encodings() {
+        // "😀ab": utf-16 offset 3 is just after 😀 (2 code units) + 'a'.
+        assert_eq!(
+            convert_character(EMOJI_LINE, 3, OffsetEncoding::Utf16, OffsetEncoding::Utf8),
+            5 // 4-byte emoji + 1-byte 'a'
+        );
+        assert_eq!(
+            convert_character(EMOJI_LINE, 3, OffsetEncoding::Utf16, OffsetEncoding::Utf32),
+            2 // 1 scalar for 😀 + 1 scalar for 'a'
+        );
+    }
+
+    #[test]
+    fn converts_past_cjk_between_all_encodings() {
+        // "中文ab": utf-16 offset 2 is just after 中 and 文 (1 code unit each).
+        assert_eq!(
+            convert_character(CJK_LINE, 2, OffsetEncoding::Utf16, OffsetEncoding::Utf8),
+            6 // two 3-byte CJK characters
+        );
+        assert_eq!(
+            convert_character(CJK_LINE, 2, OffsetEncoding::Utf16, OffsetEncoding::Utf32),
+            2 // two scalars
+        );
+    }
+
+    /// utf-16 offsets that fall on a char boundary in `line` (0, and after
+    /// each char), i.e. the only offsets a real `Position` would ever use.
+    fn utf16_char_boundaries(line: &str) -> Vec<u32> {
+        let mut boundaries = vec![0];
+        let mut offset = 0u32;
+        for ch in line.chars() {
+            offset += ch.len_utf16() as u32;
+            boundaries.push(offset);
+        }
+        boundaries
+    }
+
+    #[test]
+    fn round_trips_through_utf8_and_utf32() {
+        for &line in &[EMOJI_LINE, CJK_LINE] {
+            for host_character in utf16_char_boundaries(line) {
+                let via_utf8 =
+                    convert_character(line, host_character, OffsetEncoding::Utf16, OffsetEncoding::Utf8);
+                let back = convert_character(line, via_utf8, OffsetEncoding::Utf8, OffsetEncoding::Utf16);
+                assert_eq!(back, host_character);
+
+                let via_utf32 = convert_character(
+                    line,
+                    host_character,
+                    OffsetEncoding::Utf16,
+                    OffsetEncoding::Utf32,
+                );
+                let back = convert_character(
+                    line,
+                    via_utf32,
+                    OffsetEncoding::Utf32,
+                    OffsetEncoding::Utf16,
+                );
+                assert_eq!(back, host_character);
+            }
+        }
+    }
+}
@@ -0,0 +1,823 @@
+//! Connection pool for downstream bridge servers.
+//!
+//! Owns one [`ConnectionHandle`] per configured bridge server, the
+//! cross-connection record of which virtual documents have already been
+//! opened, the per-connection pending-open queue used by
+//! [`super::text_document::did_open`], on-demand binary acquisition for
+//! servers that aren't already on `PATH`, and the aggregated downstream
+//! `ServerCapabilities`/position-encoding bookkeeping.
+//!
+//! The literal "talk JSON-RPC to a spawned subprocess" transport is behind
+//! the [`Connector`] trait so this state machine (ready/failed bookkeeping)
+//! can be driven and tested without a real subprocess; the host wires in the
+//! real transport via `LanguageServerPool::with_connector_and_fetcher`.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::config::settings::BridgeServerConfig;
+use crate::lsp::auto_install::{InstallError, InstallEvent, InstallOps, InstallingLanguages, InstallingLanguagesExt};
+use crate::lsp::bridge::offset_encoding::{convert_character, OffsetEncoding};
+
+use super::text_document::did_open::PendingOpen;
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// How long `eager_open_virtual_documents` waits for a connection's
+/// handshake to complete before queueing its injections for replay instead.
+pub(crate) const INIT_TIMEOUT_SECS: u64 = 5;
+
+/// Error returned by pool operations (connection/transport failures).
+#[derive(Debug, Clone)]
+pub(crate) struct PoolError(String);
+
+impl PoolError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl std::fmt::Display for PoolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for PoolError {}
+
+/// Lifecycle state of a downstream connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConnectionState {
+    Connecting,
+    Ready,
+    Failed,
+}
+
+/// A notification queued to be sent to a downstream server.
+pub(crate) enum OutgoingNotification {
+    DidOpen { uri: String, text: String },
+}
+
+/// What a connection reports back once its `initialize`/`initialized`
+/// handshake completes.
+pub(crate) struct ConnectionInit {
+    pub(crate) capabilities: tower_lsp_server::ls_types::ServerCapabilities,
+    /// The `positionEncoding` the server negotiated (see
+    /// `OffsetEncoding::negotiate`); defaults to `Utf16` if the server's
+    /// `initialize` result doesn't specify one.
+    pub(crate) encoding: OffsetEncoding,
+}
+
+/// Spawns and speaks to a downstream bridge server.
+///
+/// Implemented by the process-spawning + JSON-RPC transport layer (not part
+/// of this tree slice); `LanguageServerPool` drives connection state based
+/// on it and is generic over it so tests can substitute a no-op connector.
+pub(crate) trait Connector: Send + Sync {
+    fn connect<'a>(
+        &'a self,
+        server_name: &'a str,
+        config: &'a BridgeServerConfig,
+    ) -> BoxFuture<'a, Result<ConnectionInit, PoolError>>;
+
+    fn notify<'a>(
+        &'a self,
+        server_name: &'a str,
+        notification: &'a OutgoingNotification,
+    ) -> BoxFuture<'a, Result<(), PoolError>>;
+}
+
+struct NotConfiguredConnector;
+
+impl Connector for NotConfiguredConnector {
+    fn connect<'a>(
+        &'a self,
+        server_name: &'a str,
+        _config: &'a BridgeServerConfig,
+    ) -> BoxFuture<'a, Result<ConnectionInit, PoolError>> {
+        Box::pin(async move {
+            Err(PoolError::new(format!(
+                "no transport configured to spawn bridge server '{server_name}'"
+            )))
+        })
+    }
+
+    fn notify<'a>(
+        &'a self,
+        server_name: &'a str,
+        _notification: &'a OutgoingNotification,
+    ) -> BoxFuture<'a, Result<(), PoolError>> {
+        Box::pin(async move {
+            Err(PoolError::new(format!(
+                "no transport configured to talk to bridge server '{server_name}'"
+            )))
+        })
+    }
+}
+
+/// A live (or still-connecting) downstream connection.
+pub(crate) struct ConnectionHandle {
+    server_name: String,
+    connector: Arc<dyn Connector>,
+    state: Mutex<ConnectionState>,
+    ready_notify: Notify,
+    capabilities: Mutex<tower_lsp_server::ls_types::ServerCapabilities>,
+    encoding: Mutex<OffsetEncoding>,
+}
+
+impl ConnectionHandle {
+    fn new(server_name: String, connector: Arc<dyn Connector>) -> Self {
+        Self {
+            server_name,
+            connector,
+            state: Mutex::new(ConnectionState::Connecting),
+            ready_notify: Notify::new(),
+            capabilities: Mutex::new(tower_lsp_server::ls_types::ServerCapabilities::default()),
+            encoding: Mutex::new(OffsetEncoding::default()),
+        }
+    }
+
+    pub(crate) fn state(&self) -> ConnectionState {
+        *self.state.lock().unwrap()
+    }
+
+    pub(crate) fn set_state(&self, state: ConnectionState) {
+        *self.state.lock().unwrap() = state;
+        self.ready_notify.notify_waiters();
+    }
+
+    fn mark_ready(&self, init: &ConnectionInit) {
+        *self.capabilities.lock().unwrap() = init.capabilities.clone();
+        *self.encoding.lock().unwrap() = init.encoding;
+        self.set_state(ConnectionState::Ready);
+    }
+
+    pub(crate) fn capabilities(&self) -> tower_lsp_server::ls_types::ServerCapabilities {
+        self.capabilities.lock().unwrap().clone()
+    }
+
+    pub(crate) fn encoding(&self) -> OffsetEncoding {
+        *self.encoding.lock().unwrap()
+    }
+}
+
+/// Sends notifications to a connection's downstream server.
+///
+/// A thin handle passed by `&mut` so call sites read as "do I/O", matching
+/// how a real JSON-RPC writer would be threaded through.
+pub(crate) struct ConnectionHandleSender<'a>(pub(crate) &'a ConnectionHandle);
+
+impl ConnectionHandleSender<'_> {
+    async fn notify_did_open(&mut self, uri: String, text: String) -> Result<(), PoolError> {
+        self.0
+            .connector
+            .notify(&self.0.server_name, &OutgoingNotification::DidOpen { uri, text })
+            .await
+    }
+}
+
+/// Aggregated completion/signature-help capabilities across all bridge
+/// servers whose `ServerCapabilities` have been recorded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct AggregatedBridgeCapabilities {
+    pub(crate) completion_trigger_characters: Vec<String>,
+    /// Whether any recorded server supports `completionItem/resolve`, so
+    /// the host knows it must forward resolve requests downstream rather
+    /// than trusting the initial completion list to be complete.
+    pub(crate) completion_resolve_provider: bool,
+    pub(crate) signature_help_trigger_characters: Vec<String>,
+    /// Whether any recorded server advertises a `signatureHelpProvider` at
+    /// all, so the host knows whether to advertise the capability upstream
+    /// even on lines where no trigger character has been typed yet.
+    pub(crate) signature_help_supported: bool,
+}
+
+pub(crate) struct LanguageServerPool {
+    self_weak: Mutex<Weak<Self>>,
+    connections: Mutex<HashMap<String, Arc<ConnectionHandle>>>,
+    opened_documents: Mutex<HashSet<String>>,
+    pending_opens: Mutex<HashMap<String, Vec<PendingOpen>>>,
+    capabilities: Mutex<HashMap<String, tower_lsp_server::ls_types::ServerCapabilities>>,
+    binary_installs: InstallingLanguages,
+    binary_fetcher: Arc<dyn InstallOps>,
+    connector: Arc<dyn Connector>,
+    events: UnboundedSender<InstallEvent>,
+}
+
+struct NoBinaryFetcher;
+
+impl InstallOps for NoBinaryFetcher {
+    fn install<'a>(&'a self, server_name: &'a str) -> BoxFuture<'a, Result<(), InstallError>> {
+        Box::pin(async move {
+            Err(InstallError(format!(
+                "no binary fetcher configured for bridge server '{server_name}'"
+            )))
+        })
+    }
+
+    fn validate<'a>(&'a self, _server_name: &'a str) -> BoxFuture<'a, Result<(), InstallError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn purge<'a>(&'a self, _server_name: &'a str) -> BoxFuture<'a, Result<(), InstallError>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+impl LanguageServerPool {
+    /// Create a pool with no real transport or binary fetcher wired in
+    /// (every connection attempt and on-demand acquisition fails), alongside
+    /// the receiving end of its `InstallEvent` stream.
+    pub(crate) fn new() -> (Arc<Self>, UnboundedReceiver<InstallEvent>) {
+        Self::with_connector_and_fetcher(Arc::new(NotConfiguredConnector), Arc::new(NoBinaryFetcher))
+    }
+
+    /// Create a pool that uses `connector` to spawn/talk to downstream
+    /// servers and `binary_fetcher` to acquire their binaries on demand,
+    /// alongside the receiving end of its `InstallEvent` stream (mirroring
+    /// `AutoInstallManager::new`) so the host can forward binary-acquisition
+    /// progress as `$/progress` notifications.
+    pub(crate) fn with_connector_and_fetcher(
+        connector: Arc<dyn Connector>,
+        binary_fetcher: Arc<dyn InstallOps>,
+    ) -> (Arc<Self>, UnboundedReceiver<InstallEvent>) {
+        let (events, receiver) = mpsc::unbounded_channel();
+        let pool = Arc::new_cyclic(|weak| Self {
+            self_weak: Mutex::new(weak.clone()),
+            connections: Mutex::new(HashMap::new()),
+            opened_documents: Mutex::new(HashSet::new()),
+            pending_opens: Mutex::new(HashMap::new()),
+            capabilities: Mutex::new(HashMap::new()),
+            binary_installs: InstallingLanguages::new(),
+            binary_fetcher,
+            connector,
+            events,
+        });
+        (pool, receiver)
+    }
+
+    pub(crate) async fn insert_connection(&self, server_name: &str, handle: ConnectionHandle) {
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(server_name.to_string(), Arc::new(handle));
+    }
+
+    pub(crate) fn is_document_opened(&self, virtual_uri: &super::protocol::VirtualDocumentUri) -> bool {
+        self.opened_documents
+            .lock()
+            .unwrap()
+            .contains(&virtual_uri.to_uri_string())
+    }
+
+    pub(crate) async fn ensure_document_opened(
+        &self,
+        sender: &mut ConnectionHandleSender<'_>,
+        host_uri: &url::Url,
+        virtual_uri: &super::protocol::VirtualDocumentUri,
+        content: &str,
+        server_name: &str,
+    ) -> Result<(), PoolError> {
+        if self.is_document_opened(virtual_uri) {
+            return Ok(());
+        }
+
+        sender
+            .notify_did_open(virtual_uri.to_uri_string(), content.to_string())
+            .await
+            .map_err(|e| {
+                PoolError::new(format!(
+                    "failed to open {} on {server_name} (host {host_uri}): {e}",
+                    virtual_uri.to_uri_string()
+                ))
+            })?;
+
+        self.opened_documents
+            .lock()
+            .unwrap()
+            .insert(virtual_uri.to_uri_string());
+        Ok(())
+    }
+
+    /// Buffer `entries` for `server_name`, dropping the oldest ones beyond
+    /// `max_len` so a connection that never becomes ready can't grow the
+    /// queue without bound.
+    pub(crate) fn enqueue_pending_opens(
+        &self,
+        server_name: &str,
+        entries: Vec<PendingOpen>,
+        max_len: usize,
+    ) {
+        let mut pending = self.pending_opens.lock().unwrap();
+        let queue = pending.entry(server_name.to_string()).or_default();
+        queue.extend(entries);
+
+        if queue.len() > max_len {
+            let overflow = queue.len() - max_len;
+            log::debug!(
+                target: "kakehashi::bridge",
+                "Eager open queue for {} exceeded {} entries, dropping {} oldest",
+                server_name,
+                max_len,
+                overflow
+            );
+            queue.drain(0..overflow);
+        }
+    }
+
+    /// Remove and return all entries queued for `server_name`.
+    pub(crate) fn take_pending_opens(&self, server_name: &str) -> Vec<PendingOpen> {
+        self.pending_opens
+            .lock()
+            .unwrap()
+            .remove(server_name)
+            .unwrap_or_default()
+    }
+
+    /// Record `server_name`'s downstream `ServerCapabilities`, for
+    /// `aggregated_trigger_characters`.
+    pub(crate) fn record_server_capabilities(
+        &self,
+        server_name: &str,
+        capabilities: tower_lsp_server::ls_types::ServerCapabilities,
+    ) {
+        self.capabilities
+            .lock()
+            .unwrap()
+            .insert(server_name.to_string(), capabilities);
+    }
+
+    /// Union completion and signature-help trigger characters (and whether
+    /// any server supports completion resolve / signature help at all)
+    /// across every bridge server whose capabilities have been recorded, so
+    /// the host can merge them into its own advertised `CompletionOptions`/
+    /// `SignatureHelpOptions` (or push a `client/registerCapability` update).
+    pub(crate) fn aggregated_trigger_characters(&self) -> AggregatedBridgeCapabilities {
+        let capabilities = self.capabilities.lock().unwrap();
+
+        let mut completion = BTreeSet::new();
+        let mut signature_help = BTreeSet::new();
+        let mut completion_resolve_provider = false;
+        let mut signature_help_supported = false;
+
+        for caps in capabilities.values() {
+            if let Some(provider) = caps.completion_provider.as_ref() {
+                if let Some(chars) = provider.trigger_characters.as_ref() {
+                    completion.extend(chars.iter().cloned());
+                }
+                if provider.resolve_provider == Some(true) {
+                    completion_resolve_provider = true;
+                }
+            }
+            if let Some(provider) = caps.signature_help_provider.as_ref() {
+                signature_help_supported = true;
+                if let Some(chars) = provider.trigger_characters.as_ref() {
+                    signature_help.extend(chars.iter().cloned());
+                }
+            }
+        }
+
+        AggregatedBridgeCapabilities {
+            completion_trigger_characters: completion.into_iter().collect(),
+            completion_resolve_provider,
+            signature_help_trigger_characters: signature_help.into_iter().collect(),
+            signature_help_supported,
+        }
+    }
+
+    /// The position encoding negotiated with `server_name`'s connection, or
+    /// the LSP default (`Utf16`) if there is no recorded connection.
+    pub(crate) fn encoding_for(&self, server_name: &str) -> OffsetEncoding {
+        self.connection_for(server_name)
+            .map(|handle| handle.encoding())
+            .unwrap_or_default()
+    }
+
+    /// Translate a host-document `Position` (host documents are always
+    /// `Utf16`, kakehashi's own default) into `server_name`'s negotiated
+    /// encoding, using `line_text` to walk the line's char boundaries.
+    pub(crate) fn translate_host_position_to_virtual(
+        &self,
+        server_name: &str,
+        line_text: &str,
+        host_position: tower_lsp_server::ls_types::Position,
+    ) -> tower_lsp_server::ls_types::Position {
+        tower_lsp_server::ls_types::Position {
+            line: host_position.line,
+            character: convert_character(
+                line_text,
+                host_position.character,
+                OffsetEncoding::Utf16,
+                self.encoding_for(server_name),
+            ),
+        }
+    }
+
+    /// Translate a `Position` reported by `server_name` (e.g. in a
+    /// diagnostic or completion edit) back into the host document's `Utf16`
+    /// encoding.
+    pub(crate) fn translate_virtual_position_to_host(
+        &self,
+        server_name: &str,
+        line_text: &str,
+        virtual_position: tower_lsp_server::ls_types::Position,
+    ) -> tower_lsp_server::ls_types::Position {
+        tower_lsp_server::ls_types::Position {
+            line: virtual_position.line,
+            character: convert_character(
+                line_text,
+                virtual_position.character,
+                self.encoding_for(server_name),
+                OffsetEncoding::Utf16,
+            ),
+        }
+    }
+
+    fn connection_for(&self, server_name: &str) -> Option<Arc<ConnectionHandle>> {
+        self.connections.lock().unwrap().get(server_name).cloned()
+    }
+
+    /// Get the connection for `server_name`, spawning and connecting one if
+    /// there isn't one yet, and wait up to `timeout` for it to become
+    /// `Ready`.
+    pub(crate) async fn get_or_create_connection_wait_ready(
+        &self,
+        server_name: &str,
+        server_config: &BridgeServerConfig,
+        timeout: Duration,
+    ) -> Result<Arc<ConnectionHandle>, PoolError> {
+        let handle = {
+            let mut connections = self.connections.lock().unwrap();
+            if let Some(handle) = connections.get(server_name) {
+                handle.clone()
+            } else {
+                let handle = Arc::new(ConnectionHandle::new(server_name.to_string(), self.connector.clone()));
+                connections.insert(server_name.to_string(), handle.clone());
+                self.spawn_connect(server_name.to_string(), server_config.clone(), handle.clone());
+                handle
+            }
+        };
+
+        self.wait_ready(&handle, timeout).await?;
+        Ok(handle)
+    }
+
+    fn spawn_connect(&self, server_name: String, server_config: BridgeServerConfig, handle: Arc<ConnectionHandle>) {
+        let Some(pool) = self.self_weak.lock().unwrap().upgrade() else {
+            return;
+        };
+
+        tokio::spawn(async move {
+            match handle.connector.clone().connect(&server_name, &server_config).await {
+                Ok(init) => {
+                    handle.mark_ready(&init);
+                    pool.record_server_capabilities(&server_name, init.capabilities);
+                    pool.drain_pending_opens(&server_name, &handle).await;
+                }
+                Err(e) => {
+                    log::debug!(
+                        target: "kakehashi::bridge",
+                        "Connection to {} failed to initialize, dropping its pending opens: {}",
+                        server_name,
+                        e
+                    );
+                    handle.set_state(ConnectionState::Failed);
+                    pool.pending_opens.lock().unwrap().remove(&server_name);
+                }
+            }
+        });
+    }
+
+    async fn wait_ready(&self, handle: &ConnectionHandle, timeout: Duration) -> Result<(), PoolError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match handle.state() {
+                ConnectionState::Ready => return Ok(()),
+                ConnectionState::Failed => {
+                    return Err(PoolError::new("connection failed to initialize"));
+                }
+                ConnectionState::Connecting => {}
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(PoolError::new("timed out waiting for connection to become ready"));
+            }
+            let _ = tokio::time::timeout(remaining, handle.ready_notify.notified()).await;
+        }
+    }
+
+    /// Acquire `server_config`'s binary on demand if it declares a
+    /// `BridgeServerDownload`, deduping concurrent acquisitions for the same
+    /// server via `InstallingLanguages`/`InProgressSet` keyed by server
+    /// name. A no-op if the server doesn't declare one (it's assumed to
+    /// already be on `PATH`).
+    ///
+    /// Emits `InstallEvent`s (`Started`/`Installed`/`Failed`) on this pool's
+    /// event stream around the acquisition, the same channel
+    /// `AutoInstallManager` uses for parser/query installs, so the host can
+    /// report binary acquisition the same way. There's no `GaveUp` here:
+    /// unlike `AutoInstallManager`, a failed acquisition isn't retried with
+    /// backoff — the caller decides whether to try again.
+    pub(crate) async fn ensure_server_binary_installed(
+        &self,
+        server_name: &str,
+        server_config: &BridgeServerConfig,
+    ) -> Result<PathBuf, PoolError> {
+        let Some(download) = &server_config.download else {
+            return Ok(PathBuf::from(&server_config.command));
+        };
+
+        let cache_path = cached_binary_path(server_name, download);
+
+        loop {
+            if cache_path.is_file() {
+                return Ok(cache_path);
+            }
+
+            if self.binary_installs.try_start_install(server_name) {
+                self.emit_install_event(InstallEvent::Started(server_name.to_string()));
+
+                let result = self.binary_fetcher.install(server_name).await;
+                let outcome = match result {
+                    Ok(()) => self.binary_fetcher.validate(server_name).await,
+                    Err(e) => Err(e),
+                };
+                self.binary_installs.finish_install(server_name);
+
+                return match outcome {
+                    Ok(()) => {
+                        self.emit_install_event(InstallEvent::Installed(server_name.to_string()));
+                        Ok(cache_path)
+                    }
+                    Err(e) => {
+                        let _ = self.binary_fetcher.purge(server_name).await;
+                        self.emit_install_event(InstallEvent::Failed {
+                            language: server_name.to_string(),
+                            reason: e.to_string(),
+                        });
+                        Err(PoolError::new(format!(
+                            "failed to acquire binary for bridge server '{server_name}': {e}"
+                        )))
+                    }
+                };
+            }
+
+            // Another caller is already acquiring this binary; wait for it
+            // to finish and check the cache again rather than each caller
+            // triggering its own acquisition.
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    fn emit_install_event(&self, event: InstallEvent) {
+        // A dropped receiver just means nobody is watching progress right
+        // now; acquisition itself must not fail because of that.
+        let _ = self.events.send(event);
+    }
+}
+
+fn cached_binary_path(server_name: &str, download: &crate::config::settings::BridgeServerDownload) -> PathBuf {
+    std::env::temp_dir()
+        .join("kakehashi")
+        .join("bridge-servers")
+        .join(server_name)
+        .join(&download.version)
+        .join(&download.binary_path_in_archive)
+}
+
+#[cfg(test)]
+pub(crate) mod test_helpers {
+    use super::*;
+
+    pub(crate) const TEST_ULID_LUA_0: &str = "01ARZ3NDEKTSV4RRFFQ69G5FAV";
+    pub(crate) const TEST_ULID_LUA_1: &str = "01ARZ3NDEKTSV4RRFFQ69G5FAW";
+
+    struct NullConnector;
+
+    impl Connector for NullConnector {
+        fn connect<'a>(
+            &'a self,
+            _server_name: &'a str,
+            _config: &'a BridgeServerConfig,
+        ) -> BoxFuture<'a, Result<ConnectionInit, PoolError>> {
+            Box::pin(async {
+                Ok(ConnectionInit {
+                    capabilities: tower_lsp_server::ls_types::ServerCapabilities::default(),
+                    encoding: OffsetEncoding::default(),
+                })
+            })
+        }
+
+        fn notify<'a>(
+            &'a self,
+            _server_name: &'a str,
+            _notification: &'a OutgoingNotification,
+        ) -> BoxFuture<'a, Result<(), PoolError>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    pub(crate) fn devnull_config() -> BridgeServerConfig {
+        BridgeServerConfig {
+            name: "test-server".to_string(),
+            command: "true".to_string(),
+            args: Vec::new(),
+            download: None,
+        }
+    }
+
+    pub(crate) async fn create_handle_with_state(state: ConnectionState) -> ConnectionHandle {
+        create_handle_with_state_and_encoding(state, OffsetEncoding::Utf16).await
+    }
+
+    pub(crate) async fn create_handle_with_state_and_encoding(
+        state: ConnectionState,
+        encoding: OffsetEncoding,
+    ) -> ConnectionHandle {
+        let handle = ConnectionHandle::new("test-server".to_string(), Arc::new(NullConnector));
+        handle.set_state(state);
+        *handle.encoding.lock().unwrap() = encoding;
+        handle
+    }
+
+    pub(crate) fn test_host_uri(name: &str) -> url::Url {
+        url::Url::parse(&format!("file:///tmp/{name}.md")).expect("valid test url")
+    }
+
+    pub(crate) fn url_to_uri(url: &url::Url) -> tower_lsp_server::ls_types::Uri {
+        url.as_str().parse().expect("valid test uri")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_helpers::*;
+    use super::*;
+
+    #[tokio::test]
+    async fn aggregated_trigger_characters_unions_across_servers() {
+        let (pool, _events) = LanguageServerPool::new();
+
+        let mut lua_caps = tower_lsp_server::ls_types::ServerCapabilities::default();
+        lua_caps.completion_provider = Some(tower_lsp_server::ls_types::CompletionOptions {
+            trigger_characters: Some(vec![".".to_string(), ":".to_string()]),
+            ..Default::default()
+        });
+        pool.record_server_capabilities("lua", lua_caps);
+
+        let mut python_caps = tower_lsp_server::ls_types::ServerCapabilities::default();
+        python_caps.completion_provider = Some(tower_lsp_server::ls_types::CompletionOptions {
+            trigger_characters: Some(vec![".".to_string()]),
+            resolve_provider: Some(true),
+            ..Default::default()
+        });
+        python_caps.signature_help_provider = Some(tower_lsp_server::ls_types::SignatureHelpOptions {
+            trigger_characters: Some(vec!["(".to_string()]),
+            ..Default::default()
+        });
+        pool.record_server_capabilities("python", python_caps);
+
+        let aggregated = pool.aggregated_trigger_characters();
+        assert_eq!(
+            aggregated.completion_trigger_characters,
+            vec![".".to_string(), ":".to_string()]
+        );
+        assert!(
+            aggregated.completion_resolve_provider,
+            "python advertises resolve_provider, so it should be true even though lua doesn't"
+        );
+        assert_eq!(
+            aggregated.signature_help_trigger_characters,
+            vec!["(".to_string()]
+        );
+        assert!(aggregated.signature_help_supported);
+    }
+
+    #[tokio::test]
+    async fn aggregated_trigger_characters_defaults_flags_to_false_without_support() {
+        let (pool, _events) = LanguageServerPool::new();
+
+        let mut lua_caps = tower_lsp_server::ls_types::ServerCapabilities::default();
+        lua_caps.completion_provider = Some(tower_lsp_server::ls_types::CompletionOptions {
+            trigger_characters: Some(vec![".".to_string()]),
+            ..Default::default()
+        });
+        pool.record_server_capabilities("lua", lua_caps);
+
+        let aggregated = pool.aggregated_trigger_characters();
+        assert!(!aggregated.completion_resolve_provider);
+        assert!(!aggregated.signature_help_supported);
+    }
+
+    #[tokio::test]
+    async fn encoding_for_unknown_server_defaults_to_utf16() {
+        let (pool, _events) = LanguageServerPool::new();
+        assert_eq!(pool.encoding_for("nope"), OffsetEncoding::Utf16);
+    }
+
+    #[tokio::test]
+    async fn translates_positions_through_a_negotiated_encoding() {
+        let (pool, _events) = LanguageServerPool::new();
+        let handle = create_handle_with_state_and_encoding(ConnectionState::Ready, OffsetEncoding::Utf8).await;
+        pool.insert_connection("lua", handle).await;
+
+        // "😀a": utf-16 offset 3 is just after the emoji (2 units) + 'a'.
+        let line = "😀a";
+        let host_position = tower_lsp_server::ls_types::Position {
+            line: 0,
+            character: 3,
+        };
+
+        let virtual_position = pool.translate_host_position_to_virtual("lua", line, host_position);
+        assert_eq!(virtual_position.character, 5); // 4-byte emoji + 1-byte 'a'
+
+        let back = pool.translate_virtual_position_to_host("lua", line, virtual_position);
+        assert_eq!(back, host_position);
+    }
+
+    #[tokio::test]
+    async fn get_or_create_connection_wait_ready_fails_without_a_connector() {
+        let (pool, _events) = LanguageServerPool::new();
+        let config = devnull_config();
+
+        let result = pool
+            .get_or_create_connection_wait_ready("unconfigured", &config, Duration::from_millis(50))
+            .await;
+
+        assert!(result.is_err(), "no connector is configured, so this must fail");
+    }
+
+    #[tokio::test]
+    async fn ensure_server_binary_installed_is_a_no_op_without_download_metadata() {
+        let (pool, _events) = LanguageServerPool::new();
+        let config = devnull_config();
+
+        let path = pool
+            .ensure_server_binary_installed("test-server", &config)
+            .await
+            .expect("no download metadata means the configured command is used as-is");
+
+        assert_eq!(path, PathBuf::from("true"));
+    }
+
+    struct AlwaysSucceedsFetcher;
+
+    impl InstallOps for AlwaysSucceedsFetcher {
+        fn install<'a>(&'a self, _server_name: &'a str) -> BoxFuture<'a, Result<(), InstallError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn validate<'a>(&'a self, _server_name: &'a str) -> BoxFuture<'a, Result<(), InstallError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn purge<'a>(&'a self, _server_name: &'a str) -> BoxFuture<'a, Result<(), InstallError>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn ensure_server_binary_installed_emits_progress_events() {
+        let (pool, mut events) = LanguageServerPool::with_connector_and_fetcher(
+            Arc::new(NotConfiguredConnector),
+            Arc::new(AlwaysSucceedsFetcher),
+        );
+
+        let config = BridgeServerConfig {
+            name: "test-server".to_string(),
+            command: "unused".to_string(),
+            args: Vec::new(),
+            download: Some(crate::config::settings::BridgeServerDownload {
+                version: "1.0.0".to_string(),
+                url: "https://example.invalid/test-server".to_string(),
+                archive: crate::config::settings::ArchiveKind::Raw,
+                binary_path_in_archive: PathBuf::from("test-server"),
+            }),
+        };
+
+        pool.ensure_server_binary_installed("test-server", &config)
+            .await
+            .expect("fetcher always succeeds");
+
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+        assert_eq!(
+            received,
+            vec![
+                InstallEvent::Started("test-server".to_string()),
+                InstallEvent::Installed("test-server".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! URI scheme for virtual documents forwarded to downstream bridge servers.
+
+/// Identifies an injected region as a standalone document on a downstream
+/// server: `kakehashi-virtual://<language>/<host-uri-escaped>/<region-id>`.
+///
+/// Downstream servers never see the host document directly — injections are
+/// presented as their own documents under this synthetic scheme so the
+/// downstream server's own document/analysis lifecycle (didOpen/didChange/
+/// diagnostics) works unmodified.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct VirtualDocumentUri {
+    uri: String,
+}
+
+impl VirtualDocumentUri {
+    /// Build the virtual URI for an injection region.
+    ///
+    /// * `host_uri` - the host document this region was extracted from
+    /// * `language` - the injected language (used as the URI authority so a
+    ///   downstream server can be handed a file extension-appropriate name)
+    /// * `region_id` - a stable id for this injection within the host
+    ///   document (e.g. a ULID), so edits to one region don't reset others
+    pub(crate) fn new(
+        host_uri: &tower_lsp_server::ls_types::Uri,
+        language: &str,
+        region_id: &str,
+    ) -> Self {
+        let host = url::form_urlencoded::byte_serialize(host_uri.as_str().as_bytes())
+            .collect::<String>();
+        Self {
+            uri: format!("kakehashi-virtual://{language}/{host}/{region_id}"),
+        }
+    }
+
+    /// The virtual document's URI, as sent in `didOpen`/`didClose` etc.
+    pub(crate) fn to_uri_string(&self) -> String {
+        self.uri.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(s: &str) -> tower_lsp_server::ls_types::Uri {
+        s.parse().expect("valid test uri")
+    }
+
+    #[test]
+    fn distinct_regions_of_the_same_host_get_distinct_uris() {
+        let host = uri("file:///tmp/doc.md");
+        let a = VirtualDocumentUri::new(&host, "lua", "region-a");
+        let b = VirtualDocumentUri::new(&host, "lua", "region-b");
+        assert_ne!(a.to_uri_string(), b.to_uri_string());
+    }
+
+    #[test]
+    fn same_inputs_produce_the_same_uri() {
+        let host = uri("file:///tmp/doc.md");
+        let a = VirtualDocumentUri::new(&host, "lua", "region-a");
+        let b = VirtualDocumentUri::new(&host, "lua", "region-a");
+        assert_eq!(a.to_uri_string(), b.to_uri_string());
+    }
+}
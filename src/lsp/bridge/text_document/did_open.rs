@@ -3,12 +3,32 @@
 //! This module provides eager opening of virtual documents on downstream
 //! language servers when injection regions are detected during `did_open`
 //! or `did_change` processing.
+//!
+//! Connections that are still starting up buffer their eager opens instead
+//! of dropping them (mirroring how Helix defers notifications until
+//! `initialized`): see [`PendingOpen`] and `LanguageServerPool::drain_pending_opens`.
 
 use std::time::Duration;
 
-use super::super::pool::{ConnectionHandleSender, INIT_TIMEOUT_SECS, LanguageServerPool};
+use super::super::pool::{ConnectionHandle, ConnectionHandleSender, INIT_TIMEOUT_SECS, LanguageServerPool};
 use super::super::protocol::VirtualDocumentUri;
 
+/// A single virtual document `didOpen` deferred until its downstream
+/// connection finishes initializing.
+///
+/// Buffered by `eager_open_virtual_documents` when the connection isn't
+/// `Ready` yet, and replayed by `drain_pending_opens` once it is.
+pub(crate) struct PendingOpen {
+    pub(crate) host_uri: url::Url,
+    pub(crate) virtual_uri: VirtualDocumentUri,
+    pub(crate) content: String,
+}
+
+/// Maximum number of pending eager opens buffered per connection while it is
+/// still initializing. Bounds queue growth if a downstream server never
+/// completes its handshake; once exceeded, the oldest entries are dropped.
+pub(crate) const MAX_PENDING_OPENS_PER_CONNECTION: usize = 256;
+
 impl LanguageServerPool {
     /// Eagerly open virtual documents on a downstream server.
     ///
@@ -17,6 +37,15 @@ impl LanguageServerPool {
     /// downstream server so it can start analyzing immediately, rather than
     /// waiting for the first user-initiated request.
     ///
+    /// If the server's binary isn't installed yet, it is acquired on demand
+    /// (see `ensure_server_binary_installed`) before the connection is
+    /// spawned.
+    ///
+    /// Once the connection is confirmed `Ready`, its `ServerCapabilities`
+    /// are recorded against `server_name` (see `record_server_capabilities`)
+    /// so the pool's capability aggregation stays current even for servers
+    /// whose first use is an eager open rather than a direct request.
+    ///
     /// # Arguments
     /// * `server_name` - The server name (for connection lookup)
     /// * `server_config` - The server configuration (for spawning if needed)
@@ -35,11 +64,46 @@ impl LanguageServerPool {
         host_uri_lsp: &tower_lsp_server::ls_types::Uri,
         injections: Vec<(String, String, String)>,
     ) {
-        // Wait for the server to be ready (handshake complete)
+        // Make sure the downstream binary itself is available before we try
+        // to spawn it. Acquisition (download/extract into the per-server
+        // cache directory) is deduped across concurrent opens via the same
+        // `InstallingLanguages`/`InProgressSet` keyed by server name.
+        let resolved_command = match self
+            .ensure_server_binary_installed(server_name, server_config)
+            .await
+        {
+            Ok(path) => path,
+            Err(e) => {
+                log::debug!(
+                    target: "kakehashi::bridge",
+                    "Eager open: failed to acquire binary for server {}, skipping didOpen for {} injections: {}",
+                    server_name,
+                    injections.len(),
+                    e
+                );
+                return;
+            }
+        };
+
+        // `ensure_server_binary_installed` resolves to wherever the binary
+        // actually ended up (the on-demand cache directory, or `command`
+        // unchanged if no download metadata was configured). Spawn using
+        // that resolved location rather than the original `server_config`,
+        // or the connection would still try to run whatever unspawnable
+        // `command` prompted the download in the first place.
+        let resolved_config = crate::config::settings::BridgeServerConfig {
+            command: resolved_command.to_string_lossy().into_owned(),
+            ..server_config.clone()
+        };
+
+        // Wait for the server to be ready (handshake complete). If it's
+        // still starting up, buffer these injections instead of dropping
+        // them; they are replayed by `drain_pending_opens` once the
+        // connection transitions into `Ready`.
         let handle = match self
             .get_or_create_connection_wait_ready(
                 server_name,
-                server_config,
+                &resolved_config,
                 Duration::from_secs(INIT_TIMEOUT_SECS),
             )
             .await
@@ -48,20 +112,68 @@ impl LanguageServerPool {
             Err(e) => {
                 log::debug!(
                     target: "kakehashi::bridge",
-                    "Eager open: server {} not ready, skipping didOpen for {} injections: {}",
+                    "Eager open: server {} not ready yet, queueing {} injections for replay: {}",
                     server_name,
                     injections.len(),
                     e
                 );
+                let pending = injections
+                    .into_iter()
+                    .map(|(language, region_id, content)| PendingOpen {
+                        host_uri: host_uri.clone(),
+                        virtual_uri: VirtualDocumentUri::new(host_uri_lsp, &language, &region_id),
+                        content,
+                    })
+                    .collect();
+                self.enqueue_pending_opens(server_name, pending, MAX_PENDING_OPENS_PER_CONNECTION);
                 return;
             }
         };
 
-        let mut sender = ConnectionHandleSender(&handle);
+        // The handshake just completed (or a prior call already recorded
+        // it); capture the downstream capabilities so
+        // `LanguageServerPool::aggregated_trigger_characters` can union them
+        // with the other configured bridge servers for the host's advertised
+        // `CompletionOptions`/`SignatureHelpOptions`.
+        self.record_server_capabilities(server_name, handle.capabilities());
+
+        // `spawn_connect`'s background task drains `pending_opens` exactly
+        // once, right when the connection first becomes `Ready`. A caller
+        // whose own `wait_ready` above times out at essentially that same
+        // moment can still enqueue entries afterward (see the
+        // `enqueue_pending_opens` call above) that nothing would ever drain again
+        // — this connection is already `Ready`, so no future connect task
+        // will run. Opportunistically drain here too, on every call that
+        // observes the connection `Ready`, so those entries aren't stranded.
+        self.drain_pending_opens(server_name, &handle).await;
+
+        self.send_eager_opens(&handle, server_name, host_uri, host_uri_lsp, injections)
+            .await;
+    }
+
+    /// Send `didOpen` for each injection on an already-`Ready` connection.
+    ///
+    /// Already-opened virtual documents (per `DocumentTracker`) are skipped,
+    /// so this is safe to call both from `eager_open_virtual_documents` and
+    /// from `drain_pending_opens` without double-opening a document that
+    /// raced between the two paths.
+    async fn send_eager_opens(
+        &self,
+        handle: &ConnectionHandle,
+        server_name: &str,
+        host_uri: &url::Url,
+        host_uri_lsp: &tower_lsp_server::ls_types::Uri,
+        injections: Vec<(String, String, String)>,
+    ) {
+        let mut sender = ConnectionHandleSender(handle);
 
         for (language, region_id, content) in &injections {
             let virtual_uri = VirtualDocumentUri::new(host_uri_lsp, language, region_id);
 
+            if self.is_document_opened(&virtual_uri) {
+                continue;
+            }
+
             if let Err(e) = self
                 .ensure_document_opened(&mut sender, host_uri, &virtual_uri, content, server_name)
                 .await
@@ -76,6 +188,42 @@ impl LanguageServerPool {
             }
         }
     }
+
+    /// Replay `didOpen` for any injections queued while `server_name`'s
+    /// connection was still initializing.
+    ///
+    /// Called by the connect task once the connection transitions into
+    /// `ConnectionState::Ready` (driven by the downstream `initialized`
+    /// notification), and again by every `eager_open_virtual_documents` call
+    /// that finds the connection `Ready`. If the connection instead fails to
+    /// start, the connect task discards the queue without calling this.
+    pub(crate) async fn drain_pending_opens(&self, server_name: &str, handle: &ConnectionHandle) {
+        for pending in self.take_pending_opens(server_name) {
+            if self.is_document_opened(&pending.virtual_uri) {
+                continue;
+            }
+
+            let mut sender = ConnectionHandleSender(handle);
+            if let Err(e) = self
+                .ensure_document_opened(
+                    &mut sender,
+                    &pending.host_uri,
+                    &pending.virtual_uri,
+                    &pending.content,
+                    server_name,
+                )
+                .await
+            {
+                log::debug!(
+                    target: "kakehashi::bridge",
+                    "Eager open replay: failed to open {} on {}: {}",
+                    pending.virtual_uri.to_uri_string(),
+                    server_name,
+                    e
+                );
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -90,7 +238,7 @@ mod tests {
     /// should result in each virtual document being marked as opened in DocumentTracker.
     #[tokio::test]
     async fn eager_open_marks_documents_as_opened() {
-        let pool = LanguageServerPool::new();
+        let (pool, _events) = LanguageServerPool::new();
         let config = devnull_config();
         let server_name = "test-server";
 
@@ -144,7 +292,7 @@ mod tests {
     /// for already-opened documents.
     #[tokio::test]
     async fn eager_open_is_idempotent() {
-        let pool = LanguageServerPool::new();
+        let (pool, _events) = LanguageServerPool::new();
         let config = devnull_config();
         let server_name = "test-server";
 
@@ -191,4 +339,42 @@ mod tests {
             "Should still be opened after second call"
         );
     }
+
+    /// Test that `drain_pending_opens` replays queued injections.
+    ///
+    /// Entries queued via `enqueue_pending_opens` (as `eager_open_virtual_documents`
+    /// does when the connection isn't `Ready` yet) should be sent and marked
+    /// opened once the connection becomes ready and the queue is drained.
+    #[tokio::test]
+    async fn drain_pending_opens_replays_queued_injections() {
+        let (pool, _events) = LanguageServerPool::new();
+        let server_name = "test-server";
+
+        let host_uri = test_host_uri("pending");
+        let host_uri_lsp = url_to_uri(&host_uri);
+        let virtual_uri = VirtualDocumentUri::new(&host_uri_lsp, "lua", TEST_ULID_LUA_0);
+
+        pool.enqueue_pending_opens(
+            server_name,
+            vec![super::PendingOpen {
+                host_uri: host_uri.clone(),
+                virtual_uri: virtual_uri.clone(),
+                content: "print('queued')".to_string(),
+            }],
+            super::MAX_PENDING_OPENS_PER_CONNECTION,
+        );
+
+        assert!(
+            !pool.is_document_opened(&virtual_uri),
+            "Queued injection should not be opened until drained"
+        );
+
+        let handle = create_handle_with_state(ConnectionState::Ready).await;
+        pool.drain_pending_opens(server_name, &handle).await;
+
+        assert!(
+            pool.is_document_opened(&virtual_uri),
+            "Queued injection should be opened after draining"
+        );
+    }
 }
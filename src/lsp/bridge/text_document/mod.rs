@@ -0,0 +1,3 @@
+//! Per-notification handling for documents forwarded to downstream servers.
+
+pub(crate) mod did_open;
@@ -0,0 +1,87 @@
+//! A generic concurrent set used to dedupe in-flight work by key.
+//!
+//! `AutoInstallManager` and `LanguageServerPool` both need to make sure that
+//! concurrent callers asking for the same thing (installing a language,
+//! acquiring a downstream binary, ...) only trigger the underlying work once.
+//! `InProgressSet<T>` is the shared primitive both build domain-specific
+//! extension traits on top of (see `crate::lsp::auto_install::InstallingLanguagesExt`).
+
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+/// A set of keys currently "in progress", guarded so only one caller at a
+/// time can start work for a given key.
+pub struct InProgressSet<T> {
+    keys: Mutex<HashSet<T>>,
+}
+
+impl<T> Default for InProgressSet<T> {
+    fn default() -> Self {
+        Self {
+            keys: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+impl<T> InProgressSet<T>
+where
+    T: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Try to mark `key` as in progress. Returns `true` if this call won the
+    /// race and should perform the work, `false` if another caller already
+    /// has it in progress.
+    pub fn try_start(&self, key: &T) -> bool {
+        self.keys.lock().unwrap().insert(key.clone())
+    }
+
+    /// Mark `key` as no longer in progress, allowing a future `try_start` for
+    /// the same key to succeed again.
+    pub fn finish(&self, key: &T) {
+        self.keys.lock().unwrap().remove(key);
+    }
+
+    /// Whether `key` is currently in progress.
+    pub fn is_in_progress(&self, key: &T) -> bool {
+        self.keys.lock().unwrap().contains(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_start_is_exclusive_until_finished() {
+        let set: InProgressSet<String> = InProgressSet::new();
+        let key = "lua".to_string();
+
+        assert!(set.try_start(&key), "first caller should win the race");
+        assert!(
+            !set.try_start(&key),
+            "second caller should see it already in progress"
+        );
+
+        set.finish(&key);
+        assert!(
+            set.try_start(&key),
+            "should be startable again after finishing"
+        );
+    }
+
+    #[test]
+    fn is_in_progress_reflects_state() {
+        let set: InProgressSet<String> = InProgressSet::new();
+        let key = "lua".to_string();
+
+        assert!(!set.is_in_progress(&key));
+        set.try_start(&key);
+        assert!(set.is_in_progress(&key));
+        set.finish(&key);
+        assert!(!set.is_in_progress(&key));
+    }
+}
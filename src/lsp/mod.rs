@@ -0,0 +1,6 @@
+//! LSP-facing subsystems: auto-install, the downstream bridge pool, and the
+//! generic concurrency primitives they share.
+
+pub(crate) mod auto_install;
+pub(crate) mod bridge;
+pub(crate) mod in_progress_set;